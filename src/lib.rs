@@ -14,23 +14,38 @@ pub enum Error {
     Unknown(c_int),
 }
 
-impl std::error::Error for Error {
-    fn description(&self) -> &str {
+impl Error {
+    /// Recovers the original liblame return code this error was built from.
+    pub fn code(&self) -> c_int {
         match *self {
-            Error::Ok => "No problem",
-            Error::GenericError => "Generic error",
-            Error::NoMem => "No memory",
-            Error::BadBitRate => "Bad bitrate",
-            Error::BadSampleFreq => "Bad sample frequency",
-            Error::InternalError => "Internal error",
-            Error::Unknown(_) => "Unknown error",
+            Error::Ok => 0,
+            Error::GenericError => -1,
+            Error::NoMem => -10,
+            Error::BadBitRate => -11,
+            Error::BadSampleFreq => -12,
+            Error::InternalError => -13,
+            Error::Unknown(code) => code,
         }
     }
 }
 
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
 impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self)
+        match *self {
+            Error::Ok => write!(f, "No problem"),
+            Error::GenericError => write!(f, "Generic error"),
+            Error::NoMem => write!(f, "No memory"),
+            Error::BadBitRate => write!(f, "Bad bitrate"),
+            Error::BadSampleFreq => write!(f, "Bad sample frequency"),
+            Error::InternalError => write!(f, "Internal error"),
+            Error::Unknown(code) => write!(f, "Unknown error ({})", code),
+        }
     }
 }
 
@@ -69,24 +84,130 @@ pub enum EncodeError {
     NoMem,
     InitParamsNotCalled,
     PsychoAcousticError,
+    /// An interleaved PCM buffer's length wasn't evenly divisible by the
+    /// configured channel count. Never produced by liblame itself; raised
+    /// by `encode_interleaved`/`encode_interleaved_float` before liblame is
+    /// called.
+    InvalidBufferLength,
     Unknown(c_int),
 }
 
-impl std::error::Error for EncodeError {
-    fn description<'a>(&'a self) -> &'a str {
+impl EncodeError {
+    /// Recovers the original liblame return code this error was built from.
+    /// `InvalidBufferLength` has no liblame equivalent, since it is caught
+    /// before liblame is ever called; it is represented by `c_int::MIN`.
+    pub fn code(&self) -> c_int {
         match *self {
-            EncodeError::OutputBufferTooSmall => "Output buffer too small",
-            EncodeError::NoMem => "No memory",
-            EncodeError::InitParamsNotCalled => "Init params not called",
-            EncodeError::PsychoAcousticError => "Psycho acoustic error",
-            EncodeError::Unknown(_) => "Unknown",
+            EncodeError::OutputBufferTooSmall => -1,
+            EncodeError::NoMem => -2,
+            EncodeError::InitParamsNotCalled => -3,
+            EncodeError::PsychoAcousticError => -4,
+            EncodeError::InvalidBufferLength => c_int::MIN,
+            EncodeError::Unknown(code) => code,
         }
     }
 }
 
+impl std::error::Error for EncodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
 impl Display for EncodeError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self)
+        match *self {
+            EncodeError::OutputBufferTooSmall => write!(f, "Output buffer too small"),
+            EncodeError::NoMem => write!(f, "No memory"),
+            EncodeError::InitParamsNotCalled => write!(f, "Init params not called"),
+            EncodeError::PsychoAcousticError => write!(f, "Psycho acoustic error"),
+            EncodeError::InvalidBufferLength => write!(
+                f,
+                "interleaved PCM buffer length is not divisible by the number of channels"
+            ),
+            EncodeError::Unknown(code) => write!(f, "Unknown error ({})", code),
+        }
+    }
+}
+
+impl From<c_int> for EncodeError {
+    fn from(errcode: c_int) -> EncodeError {
+        match errcode {
+            -1 => EncodeError::OutputBufferTooSmall,
+            -2 => EncodeError::NoMem,
+            -3 => EncodeError::InitParamsNotCalled,
+            -4 => EncodeError::PsychoAcousticError,
+            c_int::MIN => EncodeError::InvalidBufferLength,
+            _ => EncodeError::Unknown(errcode),
+        }
+    }
+}
+
+/// Selects LAME's variable bitrate algorithm. VBR and ABR modes pick a
+/// bitrate per frame to hit a target quality/size rather than a fixed rate,
+/// so `set_vbr_quality` is used instead of `set_kilobitrate` to control
+/// output size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VbrMode {
+    Off,
+    Rh,
+    Abr,
+    Mtrh,
+    Default,
+}
+
+impl From<VbrMode> for lame_sys::vbr_mode {
+    fn from(mode: VbrMode) -> lame_sys::vbr_mode {
+        match mode {
+            VbrMode::Off => lame_sys::vbr_mode_vbr_off,
+            VbrMode::Rh => lame_sys::vbr_mode_vbr_rh,
+            VbrMode::Abr => lame_sys::vbr_mode_vbr_abr,
+            VbrMode::Mtrh => lame_sys::vbr_mode_vbr_mtrh,
+            VbrMode::Default => lame_sys::vbr_mode_vbr_default,
+        }
+    }
+}
+
+impl From<lame_sys::vbr_mode> for VbrMode {
+    fn from(mode: lame_sys::vbr_mode) -> VbrMode {
+        match mode {
+            lame_sys::vbr_mode_vbr_off => VbrMode::Off,
+            lame_sys::vbr_mode_vbr_rh => VbrMode::Rh,
+            lame_sys::vbr_mode_vbr_abr => VbrMode::Abr,
+            lame_sys::vbr_mode_vbr_mtrh => VbrMode::Mtrh,
+            _ => VbrMode::Default,
+        }
+    }
+}
+
+/// Selects how channels are combined during encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Mono,
+    Stereo,
+    JointStereo,
+    DualChannel,
+}
+
+impl From<Mode> for lame_sys::MPEG_mode {
+    fn from(mode: Mode) -> lame_sys::MPEG_mode {
+        match mode {
+            Mode::Mono => lame_sys::MPEG_mode_MONO,
+            Mode::Stereo => lame_sys::MPEG_mode_STEREO,
+            Mode::JointStereo => lame_sys::MPEG_mode_JOINT_STEREO,
+            Mode::DualChannel => lame_sys::MPEG_mode_DUAL_CHANNEL,
+        }
+    }
+}
+
+impl From<lame_sys::MPEG_mode> for Mode {
+    fn from(mode: lame_sys::MPEG_mode) -> Mode {
+        match mode {
+            lame_sys::MPEG_mode_MONO => Mode::Mono,
+            lame_sys::MPEG_mode_JOINT_STEREO => Mode::JointStereo,
+            lame_sys::MPEG_mode_DUAL_CHANNEL => Mode::DualChannel,
+            _ => Mode::Stereo,
+        }
     }
 }
 
@@ -121,6 +242,29 @@ impl Lame {
         })
     }
 
+    /// Sample rate of the encoded MP3 output. Defaults to 0, meaning LAME
+    /// picks a rate based on the input sample rate and bitrate.
+    pub fn out_sample_rate(&self) -> u32 {
+        unsafe { lame_sys::lame_get_out_samplerate(self.ptr) as u32 }
+    }
+
+    /// Forces LAME to resample to `sample_rate` during encoding, e.g. to
+    /// downsample a 48 kHz source to 22050 Hz.
+    pub fn set_out_sample_rate(&mut self, sample_rate: u32) -> Result<(), Error> {
+        handle_simple_error(unsafe {
+            lame_sys::lame_set_out_samplerate(self.ptr, sample_rate as c_int)
+        })
+    }
+
+    /// Returns the LAME version string, e.g. `"3.100"`.
+    pub fn version() -> String {
+        unsafe {
+            std::ffi::CStr::from_ptr(lame_sys::lame_get_version())
+                .to_string_lossy()
+                .into_owned()
+        }
+    }
+
     /// Number of channels in input stream. Defaults to 2.
     pub fn channels(&self) -> u8 {
         unsafe { lame_sys::lame_get_num_channels(self.ptr) as u8 }
@@ -131,6 +275,23 @@ impl Lame {
         handle_simple_error(unsafe { lame_sys::lame_set_num_channels(self.ptr, channels as c_int) })
     }
 
+    /// Returns the channel mode LAME will encode with.
+    pub fn mode(&self) -> Mode {
+        unsafe { lame_sys::lame_get_mode(self.ptr).into() }
+    }
+
+    /// Forces a specific channel mode (mono, stereo, joint stereo or dual
+    /// channel) instead of letting LAME choose one based on `channels`.
+    pub fn set_mode(&mut self, mode: Mode) -> Result<(), Error> {
+        handle_simple_error(unsafe { lame_sys::lame_set_mode(self.ptr, mode.into()) })
+    }
+
+    /// Number of samples LAME expects per encoding pass, for the current
+    /// configuration. Useful for sizing `mp3_buffer` ahead of time.
+    pub fn frame_size(&self) -> u32 {
+        unsafe { lame_sys::lame_get_framesize(self.ptr) as u32 }
+    }
+
     /// LAME quality parameter. See `set_quality` for more details.
     pub fn quality(&self) -> u8 {
         unsafe { lame_sys::lame_get_quality(self.ptr) as u8 }
@@ -157,12 +318,149 @@ impl Lame {
         handle_simple_error(unsafe { lame_sys::lame_set_brate(self.ptr, quality as c_int) })
     }
 
+    /// Returns the current variable bitrate mode. Defaults to `VbrMode::Off`
+    /// (constant bitrate).
+    pub fn vbr_mode(&self) -> VbrMode {
+        unsafe { lame_sys::lame_get_VBR(self.ptr).into() }
+    }
+
+    /// Selects constant, average or variable bitrate encoding. When a mode
+    /// other than `VbrMode::Off` is selected, `set_vbr_quality` controls the
+    /// output size/quality tradeoff instead of `set_kilobitrate`.
+    pub fn set_vbr_mode(&mut self, mode: VbrMode) -> Result<(), Error> {
+        handle_simple_error(unsafe { lame_sys::lame_set_VBR(self.ptr, mode.into()) })
+    }
+
+    /// Sets the VBR quality level, from 0 (best, largest files) to 9 (worst,
+    /// smallest files). Only meaningful once a VBR/ABR mode has been
+    /// selected with `set_vbr_mode`.
+    pub fn set_vbr_quality(&mut self, quality: u8) -> Result<(), Error> {
+        handle_simple_error(unsafe {
+            lame_sys::lame_set_VBR_q(self.ptr, quality as c_int)
+        })
+    }
+
+    /// Sets the minimum allowed bitrate (in kbps) for VBR/ABR encoding.
+    pub fn set_vbr_min_bitrate(&mut self, bitrate: i32) -> Result<(), Error> {
+        handle_simple_error(unsafe {
+            lame_sys::lame_set_VBR_min_bitrate_kbps(self.ptr, bitrate as c_int)
+        })
+    }
+
+    /// Sets the maximum allowed bitrate (in kbps) for VBR/ABR encoding.
+    pub fn set_vbr_max_bitrate(&mut self, bitrate: i32) -> Result<(), Error> {
+        handle_simple_error(unsafe {
+            lame_sys::lame_set_VBR_max_bitrate_kbps(self.ptr, bitrate as c_int)
+        })
+    }
+
+    /// Controls whether `init_params` reserves space in the output stream
+    /// for the LAME/Xing VBR tag, which players use to seek and to report
+    /// accurate duration for VBR files. Retrieve the tag itself with
+    /// `get_vbr_tag` once encoding has finished.
+    pub fn set_write_vbr_tag(&mut self, write: bool) -> Result<(), Error> {
+        handle_simple_error(unsafe { lame_sys::lame_set_bWriteVbrTag(self.ptr, write as c_int) })
+    }
+
+    /// Renders the LAME/Xing VBR tag frame into `mp3_buffer` and returns the
+    /// number of bytes written. Call this after `encode_flush`/
+    /// `encode_flush_nogap`, then write the result back over the first frame
+    /// of the finished file (reserved for this purpose by `init_params` when
+    /// `set_write_vbr_tag(true)` was set beforehand).
+    pub fn get_vbr_tag(&self, mp3_buffer: &mut [u8]) -> usize {
+        unsafe {
+            lame_sys::lame_get_lametag_frame(
+                self.ptr,
+                mp3_buffer.as_mut_ptr(),
+                mp3_buffer.len(),
+            ) as usize
+        }
+    }
+
+    /// Whether the bit reservoir is disabled. Defaults to `false`.
+    pub fn disable_reservoir(&self) -> bool {
+        unsafe { lame_sys::lame_get_disable_reservoir(self.ptr) != 0 }
+    }
+
+    /// Disables the bit reservoir, which normally lets LAME borrow bits from
+    /// neighbouring frames to maintain quality at a given bitrate. Streaming
+    /// encoders (Icecast/shoutcast, MPD) must set this to `true` so every
+    /// frame is self-contained and the stream can be cut or joined at
+    /// arbitrary frame boundaries.
+    pub fn set_disable_reservoir(&mut self, disable: bool) -> Result<(), Error> {
+        handle_simple_error(unsafe {
+            lame_sys::lame_set_disable_reservoir(self.ptr, disable as c_int)
+        })
+    }
+
+    /// Whether LAME computes ReplayGain/peak-sample loudness analysis as a
+    /// side effect of encoding. Defaults to `false`.
+    pub fn find_replay_gain(&self) -> bool {
+        unsafe { lame_sys::lame_get_findReplayGain(self.ptr) != 0 }
+    }
+
+    /// Enables ReplayGain and peak-sample analysis during encoding. Once
+    /// encoding has finished (after `encode_flush`/`encode_flush_nogap`),
+    /// read the results back with `peak_sample`, `radio_gain` and
+    /// `audiophile_gain` to write loudness metadata into the file's tags.
+    pub fn set_find_replay_gain(&mut self, find: bool) -> Result<(), Error> {
+        handle_simple_error(unsafe {
+            lame_sys::lame_set_findReplayGain(self.ptr, find as c_int)
+        })
+    }
+
+    /// The highest absolute sample value seen while encoding. Only valid
+    /// once `encode_flush`/`encode_flush_nogap` has been called.
+    pub fn peak_sample(&self) -> f32 {
+        unsafe { lame_sys::lame_get_PeakSample(self.ptr) }
+    }
+
+    /// The suggested ReplayGain radio (track) gain, in units of 0.1 dB. Only
+    /// valid once `encode_flush`/`encode_flush_nogap` has been called, and
+    /// `set_find_replay_gain(true)` was set before encoding.
+    pub fn radio_gain(&self) -> f32 {
+        unsafe { lame_sys::lame_get_RadioGain(self.ptr) as f32 }
+    }
+
+    /// The suggested ReplayGain audiophile (album) gain, in units of 0.1 dB.
+    /// Only valid once `encode_flush`/`encode_flush_nogap` has been called,
+    /// and `set_find_replay_gain(true)` was set before encoding.
+    pub fn audiophile_gain(&self) -> f32 {
+        unsafe { lame_sys::lame_get_AudiophileGain(self.ptr) as f32 }
+    }
+
     /// Sets more internal parameters according to the other basic parameter
     /// settings.
     pub fn init_params(&mut self) -> Result<(), Error> {
         handle_simple_error(unsafe { lame_sys::lame_init_params(self.ptr) })
     }
 
+    /// Summarizes the effective encoder configuration (version, mode,
+    /// bitrate, sample rates, VBR settings) the way `lame_print_config`
+    /// would log it, for servers that want to record their startup settings
+    /// without scraping liblame's stderr output. Call after `init_params`.
+    pub fn config_summary(&self) -> String {
+        let out_sample_rate = if self.out_sample_rate() == 0 {
+            self.sample_rate()
+        } else {
+            self.out_sample_rate()
+        };
+
+        format!(
+            "LAME {} ({:.1}kHz -> {:.1}kHz, {:?}, {}kbps, quality {}{})",
+            Lame::version(),
+            self.sample_rate() as f32 / 1000.0,
+            out_sample_rate as f32 / 1000.0,
+            self.mode(),
+            self.kilobitrate(),
+            self.quality(),
+            match self.vbr_mode() {
+                VbrMode::Off => String::new(),
+                mode => format!(", VBR {:?}", mode),
+            }
+        )
+    }
+
     /// Encodes PCM data into MP3 frames. The `pcm_left` and `pcm_right`
     /// buffers must be of the same length, or this function will panic.
     pub fn encode(
@@ -186,19 +484,134 @@ impl Lame {
             )
         };
 
-        match retn {
-            -1 => Err(EncodeError::OutputBufferTooSmall),
-            -2 => Err(EncodeError::NoMem),
-            -3 => Err(EncodeError::InitParamsNotCalled),
-            -4 => Err(EncodeError::PsychoAcousticError),
-            _ => {
-                if retn < 0 {
-                    Err(EncodeError::Unknown(retn))
-                } else {
-                    Ok(retn as usize)
-                }
-            }
+        handle_encode_error(retn)
+    }
+
+    /// Encodes interleaved PCM data (e.g. `[L, R, L, R, ...]`) into MP3
+    /// frames. Returns `Err(EncodeError::InvalidBufferLength)` if `pcm.len()`
+    /// is not evenly divisible by `channels()`.
+    pub fn encode_interleaved(
+        &mut self,
+        pcm: &mut [i16],
+        mp3_buffer: &mut [u8],
+    ) -> Result<usize, EncodeError> {
+        let num_samples = self.interleaved_sample_count(pcm.len())?;
+
+        let retn = unsafe {
+            lame_sys::lame_encode_buffer_interleaved(
+                self.ptr,
+                pcm.as_mut_ptr(),
+                int_size(num_samples),
+                mp3_buffer.as_mut_ptr(),
+                int_size(mp3_buffer.len()),
+            )
+        };
+
+        handle_encode_error(retn)
+    }
+
+    /// Encodes floating-point PCM data into MP3 frames. The `pcm_left` and
+    /// `pcm_right` buffers must be of the same length, or this function will
+    /// panic. Samples are expected in the range `-1.0..=1.0`.
+    pub fn encode_float(
+        &mut self,
+        pcm_left: &mut [f32],
+        pcm_right: &mut [f32],
+        mp3_buffer: &mut [u8],
+    ) -> Result<usize, EncodeError> {
+        if pcm_left.len() != pcm_right.len() {
+            panic!("left and right channels must have same number of samples!");
         }
+
+        let retn = unsafe {
+            lame_sys::lame_encode_buffer_ieee_float(
+                self.ptr,
+                pcm_left.as_ptr(),
+                pcm_right.as_ptr(),
+                int_size(pcm_left.len()),
+                mp3_buffer.as_mut_ptr(),
+                int_size(mp3_buffer.len()),
+            )
+        };
+
+        handle_encode_error(retn)
+    }
+
+    /// Encodes interleaved floating-point PCM data into MP3 frames. Returns
+    /// `Err(EncodeError::InvalidBufferLength)` if `pcm.len()` is not evenly
+    /// divisible by `channels()`. Samples are expected in the range
+    /// `-1.0..=1.0`.
+    pub fn encode_interleaved_float(
+        &mut self,
+        pcm: &mut [f32],
+        mp3_buffer: &mut [u8],
+    ) -> Result<usize, EncodeError> {
+        let num_samples = self.interleaved_sample_count(pcm.len())?;
+
+        let retn = unsafe {
+            lame_sys::lame_encode_buffer_interleaved_ieee_float(
+                self.ptr,
+                pcm.as_ptr(),
+                int_size(num_samples),
+                mp3_buffer.as_mut_ptr(),
+                int_size(mp3_buffer.len()),
+            )
+        };
+
+        handle_encode_error(retn)
+    }
+
+    /// Converts an interleaved buffer length into a per-channel sample
+    /// count, returning `EncodeError::InvalidBufferLength` if it isn't
+    /// evenly divisible by `channels()` (including when `channels()` is 0).
+    fn interleaved_sample_count(&self, pcm_len: usize) -> Result<usize, EncodeError> {
+        let channels = self.channels() as usize;
+        if channels == 0 || pcm_len % channels != 0 {
+            return Err(EncodeError::InvalidBufferLength);
+        }
+
+        Ok(pcm_len / channels)
+    }
+
+    /// Flushes any PCM data remaining inside LAME's internal buffers into
+    /// one final MP3 frame, and writes the LAME/Xing info tag padding.
+    ///
+    /// This must be called exactly once, after the last call to `encode`,
+    /// or the final ~1152 samples of audio will be missing from the output.
+    pub fn encode_flush(&mut self, mp3_buffer: &mut [u8]) -> Result<usize, EncodeError> {
+        let retn = unsafe {
+            lame_sys::lame_encode_flush(
+                self.ptr,
+                mp3_buffer.as_mut_ptr(),
+                int_size(mp3_buffer.len()),
+            )
+        };
+
+        handle_encode_error(retn)
+    }
+
+    /// Like `encode_flush`, but omits the padding that `encode_flush` inserts
+    /// so that the resulting MP3 stream can be concatenated gaplessly with
+    /// another LAME-encoded stream. Must also be called exactly once, after
+    /// the last call to `encode`.
+    pub fn encode_flush_nogap(&mut self, mp3_buffer: &mut [u8]) -> Result<usize, EncodeError> {
+        let retn = unsafe {
+            lame_sys::lame_encode_flush_nogap(
+                self.ptr,
+                mp3_buffer.as_mut_ptr(),
+                int_size(mp3_buffer.len()),
+            )
+        };
+
+        handle_encode_error(retn)
+    }
+}
+
+fn handle_encode_error(retn: c_int) -> Result<usize, EncodeError> {
+    if retn < 0 {
+        Err(retn.into())
+    } else {
+        Ok(retn as usize)
     }
 }
 
@@ -207,3 +620,51 @@ impl Drop for Lame {
         unsafe { lame_sys::lame_close(self.ptr) };
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interleaved_sample_count_divides_by_channels() {
+        let mut lame = Lame::new().unwrap();
+        lame.set_channels(2).unwrap();
+
+        assert_eq!(lame.interleaved_sample_count(4).unwrap(), 2);
+    }
+
+    #[test]
+    fn interleaved_sample_count_rejects_misaligned_buffer() {
+        let mut lame = Lame::new().unwrap();
+        lame.set_channels(2).unwrap();
+
+        match lame.interleaved_sample_count(3) {
+            Err(EncodeError::InvalidBufferLength) => {}
+            other => panic!("expected InvalidBufferLength, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn interleaved_sample_count_rejects_zero_channels() {
+        let mut lame = Lame::new().unwrap();
+        lame.set_channels(0).unwrap();
+
+        match lame.interleaved_sample_count(4) {
+            Err(EncodeError::InvalidBufferLength) => {}
+            other => panic!("expected InvalidBufferLength, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn config_summary_reports_sample_rates_in_khz() {
+        let mut lame = Lame::new().unwrap();
+        lame.set_sample_rate(44100).unwrap();
+
+        let summary = lame.config_summary();
+        assert!(
+            summary.contains("44.1kHz"),
+            "expected summary to contain 44.1kHz, got: {}",
+            summary
+        );
+    }
+}